@@ -0,0 +1,247 @@
+use crate::{build_render_request, get_binary, parse_rpc_line, MermkitError, RenderOutcome, RenderResult};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::{oneshot, Mutex};
+use tokio::task::JoinHandle;
+
+type PendingMap = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<RenderResult, MermkitError>>>>>;
+
+/// Async counterpart to [`Client`](crate::Client), built on
+/// `tokio::process` so a server can fan many renders out to one `serve`
+/// subprocess without blocking an executor thread on `read_line`.
+///
+/// A background task owns the child's stdout and dispatches each response
+/// line to the `oneshot` channel registered for its request id, so several
+/// `.await`ed [`render`](AsyncClient::render) calls can be in flight at
+/// once without any polling from the caller.
+pub struct AsyncClient {
+    stdin: Mutex<ChildStdin>,
+    next_id: AtomicU64,
+    pending: PendingMap,
+    _child: Child,
+    reader: JoinHandle<()>,
+}
+
+impl AsyncClient {
+    pub async fn new() -> Result<Self, MermkitError> {
+        let mut child = Command::new(get_binary())
+            .arg("serve")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(MermkitError::Spawn)?;
+
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| MermkitError::Protocol("failed to open stdin".to_string()))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| MermkitError::Protocol("failed to open stdout".to_string()))?;
+
+        let pending: PendingMap = Arc::new(Mutex::new(HashMap::new()));
+        let reader = tokio::spawn(read_responses(stdout, pending.clone()));
+
+        Ok(Self {
+            stdin: Mutex::new(stdin),
+            next_id: AtomicU64::new(1),
+            pending,
+            _child: child,
+            reader,
+        })
+    }
+
+    pub async fn render(
+        &self,
+        source: &str,
+        format: &str,
+        theme: Option<&str>,
+        engine: Option<&str>,
+    ) -> Result<RenderResult, MermkitError> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        // Binary transport needs the reader task to read raw bytes straight
+        // off the pipe between response lines, which the `lines()` iterator
+        // below doesn't support; always request base64 for now.
+        let request = build_render_request(id, source, format, theme, engine, false);
+        let line = serde_json::to_string(&request).map_err(|e| MermkitError::Protocol(e.to_string()))?;
+
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().await.insert(id, tx);
+
+        let mut stdin = self.stdin.lock().await;
+        let write_result = write_request(&mut *stdin, &line).await;
+        drop(stdin);
+        if let Err(e) = write_result {
+            self.pending.lock().await.remove(&id);
+            return Err(e);
+        }
+
+        rx.await.unwrap_or(Err(MermkitError::UnexpectedEof))
+    }
+}
+
+impl Drop for AsyncClient {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+async fn write_request(stdin: &mut (impl AsyncWrite + Unpin), line: &str) -> Result<(), MermkitError> {
+    stdin
+        .write_all(line.as_bytes())
+        .await
+        .map_err(|e| MermkitError::Protocol(e.to_string()))?;
+    stdin.write_all(b"\n").await.map_err(|e| MermkitError::Protocol(e.to_string()))?;
+    stdin.flush().await.map_err(|e| MermkitError::Protocol(e.to_string()))
+}
+
+/// Reads response lines off `stdout` for as long as it stays open,
+/// dispatching each to the `oneshot` sender registered for its request id.
+/// When the pipe closes or a line can't be parsed, every still pending
+/// request is woken with an error instead of being left to hang.
+///
+/// Generic over the reader (rather than tied to `tokio::process::ChildStdout`)
+/// so the dispatch logic can be exercised in tests against a
+/// `tokio::io::duplex` pair instead of a real subprocess.
+async fn read_responses(stdout: impl AsyncRead + Unpin, pending: PendingMap) {
+    let mut lines = BufReader::new(stdout).lines();
+    loop {
+        match lines.next_line().await {
+            Ok(Some(line)) => match parse_rpc_line(&line) {
+                Ok((id, outcome)) => {
+                    let result = match outcome {
+                        RenderOutcome::Ready(result) => result,
+                        RenderOutcome::Binary { .. } => Err(MermkitError::Protocol(
+                            "mermkit sent a binary-transport response, which AsyncClient cannot read yet"
+                                .to_string(),
+                        )),
+                    };
+                    if let Some(tx) = pending.lock().await.remove(&id) {
+                        let _ = tx.send(result);
+                    }
+                }
+                Err(e) => {
+                    let message = e.to_string();
+                    fail_all(&pending, move || MermkitError::Protocol(message.clone())).await;
+                }
+            },
+            Ok(None) => {
+                fail_all(&pending, || MermkitError::UnexpectedEof).await;
+                break;
+            }
+            Err(e) => {
+                let message = e.to_string();
+                fail_all(&pending, move || MermkitError::Protocol(message.clone())).await;
+                break;
+            }
+        }
+    }
+}
+
+/// Wake every still-outstanding request with a fresh error instead of
+/// leaving its caller's `render` call hanging on the subprocess forever.
+async fn fail_all(pending: &PendingMap, make_err: impl Fn() -> MermkitError) {
+    let mut pending = pending.lock().await;
+    for (_, tx) in pending.drain() {
+        let _ = tx.send(Err(make_err()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn register(pending: &PendingMap, id: u64) -> oneshot::Receiver<Result<RenderResult, MermkitError>> {
+        let (tx, rx) = oneshot::channel();
+        pending.try_lock().expect("uncontended in tests").insert(id, tx);
+        rx
+    }
+
+    #[tokio::test]
+    async fn dispatches_a_response_to_the_matching_pending_id() {
+        let (mut wire, stdout) = tokio::io::duplex(4096);
+        let pending: PendingMap = Arc::new(Mutex::new(HashMap::new()));
+        let mut rx1 = register(&pending, 1);
+        let rx2 = register(&pending, 2);
+
+        let reader = tokio::spawn(read_responses(stdout, pending.clone()));
+
+        wire.write_all(br#"{"jsonrpc":"2.0","id":2,"result":{"bytes":"aGk=","mime":"text/plain"}}"#)
+            .await
+            .unwrap();
+        wire.write_all(b"\n").await.unwrap();
+
+        let result2 = rx2.await.expect("id 2's oneshot should be resolved");
+        assert_eq!(result2.unwrap().bytes, b"hi");
+
+        // id 1 was never answered, so its receiver must still be waiting,
+        // not woken by the response meant for id 2.
+        assert!(rx1.try_recv().is_err());
+
+        drop(wire);
+        reader.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn eof_fails_every_still_outstanding_request() {
+        let (wire, stdout) = tokio::io::duplex(4096);
+        let pending: PendingMap = Arc::new(Mutex::new(HashMap::new()));
+        let rx1 = register(&pending, 1);
+        let rx2 = register(&pending, 2);
+
+        let reader = tokio::spawn(read_responses(stdout, pending.clone()));
+
+        // Closing the write half produces EOF on `stdout` without ever
+        // sending a response line.
+        drop(wire);
+        reader.await.unwrap();
+
+        assert!(matches!(rx1.await.unwrap(), Err(MermkitError::UnexpectedEof)));
+        assert!(matches!(rx2.await.unwrap(), Err(MermkitError::UnexpectedEof)));
+        assert!(pending.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_malformed_line_does_not_stop_dispatch_for_later_requests() {
+        let (mut wire, stdout) = tokio::io::duplex(4096);
+        let pending: PendingMap = Arc::new(Mutex::new(HashMap::new()));
+        let rx1 = register(&pending, 1);
+
+        let reader = tokio::spawn(read_responses(stdout, pending.clone()));
+
+        wire.write_all(b"not json at all\n").await.unwrap();
+        assert!(matches!(rx1.await.unwrap(), Err(MermkitError::Protocol(_))));
+
+        // A request submitted after the malformed line must still be
+        // dispatched correctly; the reader loop shouldn't have torn itself
+        // down when it failed to parse the earlier line.
+        let rx2 = register(&pending, 2);
+        wire.write_all(br#"{"jsonrpc":"2.0","id":2,"result":{"bytes":"aGk=","mime":"text/plain"}}"#)
+            .await
+            .unwrap();
+        wire.write_all(b"\n").await.unwrap();
+
+        let result2 = rx2.await.expect("id 2's oneshot should be resolved");
+        assert_eq!(result2.unwrap().bytes, b"hi");
+
+        drop(wire);
+        reader.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn write_request_frames_exactly_one_newline_terminated_line() {
+        let (mut write_end, mut read_end) = tokio::io::duplex(4096);
+        write_request(&mut write_end, "hello").await.unwrap();
+        drop(write_end);
+
+        let mut received = String::new();
+        tokio::io::AsyncReadExt::read_to_string(&mut read_end, &mut received).await.unwrap();
+        assert_eq!(received, "hello\n");
+    }
+}
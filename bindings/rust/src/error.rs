@@ -0,0 +1,140 @@
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong talking to the `mermkit` binary, whether
+/// through the one-shot CLI invocation or the long-lived `serve` subprocess.
+///
+/// Each variant maps to a stable [`class`](MermkitError::class) string so
+/// callers can branch on failure kind without matching on message text.
+#[derive(Debug)]
+pub enum MermkitError {
+    /// The `mermkit` binary could not be spawned (not on `PATH`, permission
+    /// denied, etc).
+    Spawn(io::Error),
+    /// The subprocess wrote a line that wasn't valid JSON, or wasn't shaped
+    /// like the response we expected; also used when reading from or
+    /// writing to the subprocess's pipes fails.
+    Protocol(String),
+    /// The subprocess closed its stdout before sending a response.
+    UnexpectedEof,
+    /// The `bytes` field of a response could not be base64-decoded.
+    Decode(base64::DecodeError),
+    /// The renderer itself rejected the diagram, with its own error class
+    /// (e.g. `"SyntaxError"`) and message.
+    Render { class: String, message: String },
+    /// The one-shot CLI process exited with a non-zero status.
+    ExitStatus { code: Option<i32>, stderr: String },
+}
+
+impl MermkitError {
+    /// A stable, machine-readable class name for this error, suitable for
+    /// programmatic branching (e.g. in build tools that want to retry
+    /// `SpawnError` but not `RenderError`).
+    ///
+    /// For [`MermkitError::Render`], this returns the generic `"RenderError"`
+    /// class; the renderer's own class string (e.g. `"SyntaxError"`) is
+    /// available on the variant itself.
+    pub fn class(&self) -> &'static str {
+        match self {
+            MermkitError::Spawn(_) => "SpawnError",
+            MermkitError::Protocol(_) => "ProtocolError",
+            MermkitError::UnexpectedEof => "UnexpectedEofError",
+            MermkitError::Decode(_) => "DecodeError",
+            MermkitError::Render { .. } => "RenderError",
+            MermkitError::ExitStatus { .. } => "ExitStatusError",
+        }
+    }
+}
+
+impl fmt::Display for MermkitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MermkitError::Spawn(e) => write!(f, "failed to spawn mermkit: {e}"),
+            MermkitError::Protocol(msg) => write!(f, "mermkit protocol error: {msg}"),
+            MermkitError::UnexpectedEof => write!(f, "mermkit subprocess closed stdout unexpectedly"),
+            MermkitError::Decode(e) => write!(f, "failed to decode mermkit response: {e}"),
+            MermkitError::Render { class, message } => write!(f, "{class}: {message}"),
+            MermkitError::ExitStatus { code, stderr } => match code {
+                Some(code) => write!(f, "mermkit exited with status {code}: {stderr}"),
+                None => write!(f, "mermkit was terminated by a signal: {stderr}"),
+            },
+        }
+    }
+}
+
+impl std::error::Error for MermkitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MermkitError::Spawn(e) => Some(e),
+            MermkitError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<base64::DecodeError> for MermkitError {
+    fn from(e: base64::DecodeError) -> Self {
+        MermkitError::Decode(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn class_names_are_stable_per_variant() {
+        assert_eq!(MermkitError::Spawn(io::Error::other("no such binary")).class(), "SpawnError");
+        assert_eq!(MermkitError::Protocol("bad json".to_string()).class(), "ProtocolError");
+        assert_eq!(MermkitError::UnexpectedEof.class(), "UnexpectedEofError");
+        assert_eq!(
+            MermkitError::Render {
+                class: "SyntaxError".to_string(),
+                message: "unexpected token".to_string(),
+            }
+            .class(),
+            "RenderError"
+        );
+        assert_eq!(
+            MermkitError::ExitStatus { code: Some(1), stderr: String::new() }.class(),
+            "ExitStatusError"
+        );
+    }
+
+    #[test]
+    fn render_display_keeps_the_renderer_own_class_string() {
+        let err = MermkitError::Render {
+            class: "SyntaxError".to_string(),
+            message: "unexpected token".to_string(),
+        };
+        assert_eq!(err.to_string(), "SyntaxError: unexpected token");
+        // `class()` is the crate's own stable classification, distinct from
+        // the renderer's class string carried on the variant.
+        assert_eq!(err.class(), "RenderError");
+    }
+
+    #[test]
+    fn exit_status_display_distinguishes_signal_from_status_code() {
+        let by_code = MermkitError::ExitStatus {
+            code: Some(2),
+            stderr: "boom".to_string(),
+        };
+        assert_eq!(by_code.to_string(), "mermkit exited with status 2: boom");
+
+        let by_signal = MermkitError::ExitStatus { code: None, stderr: "boom".to_string() };
+        assert_eq!(by_signal.to_string(), "mermkit was terminated by a signal: boom");
+    }
+
+    #[test]
+    fn spawn_and_decode_errors_report_their_source() {
+        use std::error::Error as _;
+
+        let spawn_err = MermkitError::Spawn(io::Error::other("no such binary"));
+        assert!(spawn_err.source().is_some());
+
+        let decode_err = MermkitError::Decode(base64::DecodeError::InvalidPadding);
+        assert!(decode_err.source().is_some());
+
+        assert!(MermkitError::UnexpectedEof.source().is_none());
+    }
+}
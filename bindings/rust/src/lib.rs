@@ -1,8 +1,21 @@
+mod error;
+mod pool;
+
+#[cfg(feature = "async")]
+mod async_client;
+
+pub use error::MermkitError;
+pub use pool::{Pool, RenderJob};
+
+#[cfg(feature = "async")]
+pub use async_client::AsyncClient;
+
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
 use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufRead, BufReader, Read, Write};
 use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
 
 #[derive(Debug)]
@@ -14,89 +27,321 @@ pub struct RenderResult {
 
 #[derive(Debug, Deserialize)]
 struct RenderPayload {
+    /// Present in base64 transport; absent when `byte_len` is used instead.
     bytes: Option<String>,
+    /// Present in binary transport: the raw payload follows this response
+    /// line on the same pipe as exactly this many bytes.
+    byte_len: Option<u64>,
     mime: Option<String>,
     warnings: Option<Vec<String>>,
 }
 
+/// The `error` object of a JSON-RPC response, per the 2.0 spec: a `code`
+/// and `message`, plus an optional `data` payload we use to carry the
+/// renderer's own error class (e.g. `"SyntaxError"`).
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    #[allow(dead_code)]
+    code: i64,
+    message: String,
+    data: Option<RpcErrorData>,
+}
+
 #[derive(Debug, Deserialize)]
-struct ServeResponse {
-    ok: bool,
+struct RpcErrorData {
+    class: Option<String>,
+}
+
+impl From<RpcError> for MermkitError {
+    fn from(e: RpcError) -> Self {
+        let class = e.data.and_then(|d| d.class).unwrap_or_else(|| "RenderError".to_string());
+        MermkitError::Render { class, message: e.message }
+    }
+}
+
+/// A JSON-RPC 2.0 response. `id` is only absent for the responses the spec
+/// reserves for malformed requests we never send (e.g. parse errors), so we
+/// still require it to match a pending `render` call.
+#[derive(Debug, Deserialize)]
+struct RpcResponse {
+    id: Option<u64>,
     result: Option<RenderPayload>,
-    error: Option<String>,
+    error: Option<RpcError>,
+}
+
+/// Build the JSON-RPC 2.0 `render` request body shared by [`Client`] and
+/// `AsyncClient`. `binary_transport` sets `options.transport` to `"binary"`
+/// so the response carries its payload as a length-prefixed raw byte
+/// stream instead of base64; omitting it keeps the default base64 mode,
+/// which older `mermkit` binaries also understand.
+pub(crate) fn build_render_request(
+    id: u64,
+    source: &str,
+    format: &str,
+    theme: Option<&str>,
+    engine: Option<&str>,
+    binary_transport: bool,
+) -> serde_json::Value {
+    let mut options = serde_json::Map::new();
+    options.insert("format".to_string(), serde_json::Value::String(format.to_string()));
+    if let Some(t) = theme {
+        options.insert("theme".to_string(), serde_json::Value::String(t.to_string()));
+    }
+    if let Some(e) = engine {
+        options.insert("engine".to_string(), serde_json::Value::String(e.to_string()));
+    }
+    if binary_transport {
+        options.insert("transport".to_string(), serde_json::Value::String("binary".to_string()));
+    }
+
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": "render",
+        "params": {
+            "diagram": source,
+            "options": options,
+        },
+        "id": id,
+    })
+}
+
+/// What to do with a parsed response: either the render outcome is already
+/// known, or (binary transport) the caller still needs to read exactly
+/// `byte_len` raw bytes off the same pipe before it has one.
+#[derive(Debug)]
+pub(crate) enum RenderOutcome {
+    Ready(Result<RenderResult, MermkitError>),
+    Binary {
+        mime: String,
+        warnings: Vec<String>,
+        byte_len: usize,
+    },
+}
+
+/// Parse one response line into the request id it answers and its outcome.
+/// Shared by [`Client::poll`] and `AsyncClient`'s background reader task.
+pub(crate) fn parse_rpc_line(line: &str) -> Result<(u64, RenderOutcome), MermkitError> {
+    let resp: RpcResponse = serde_json::from_str(line).map_err(|e| MermkitError::Protocol(e.to_string()))?;
+    let id = resp
+        .id
+        .ok_or_else(|| MermkitError::Protocol("response missing id".to_string()))?;
+
+    let outcome = if let Some(error) = resp.error {
+        RenderOutcome::Ready(Err(MermkitError::from(error)))
+    } else {
+        match resp.result {
+            Some(payload) => payload_outcome(payload),
+            None => RenderOutcome::Ready(Err(MermkitError::Protocol("missing result".to_string()))),
+        }
+    };
+
+    Ok((id, outcome))
+}
+
+fn payload_outcome(payload: RenderPayload) -> RenderOutcome {
+    let mime = payload.mime.unwrap_or_else(|| "application/octet-stream".to_string());
+    let warnings = payload.warnings.unwrap_or_default();
+
+    match (payload.bytes, payload.byte_len) {
+        (Some(bytes_b64), _) => RenderOutcome::Ready(decode_base64_payload(bytes_b64, mime, warnings)),
+        (None, Some(byte_len)) => RenderOutcome::Binary {
+            mime,
+            warnings,
+            byte_len: byte_len as usize,
+        },
+        (None, None) => {
+            RenderOutcome::Ready(Err(MermkitError::Protocol("mermkit render returned no bytes".to_string())))
+        }
+    }
+}
+
+fn decode_base64_payload(bytes_b64: String, mime: String, warnings: Vec<String>) -> Result<RenderResult, MermkitError> {
+    let bytes = STANDARD.decode(bytes_b64)?;
+    Ok(RenderResult { bytes, mime, warnings })
 }
 
 pub struct Client {
     child: Child,
     stdin: ChildStdin,
     stdout: BufReader<ChildStdout>,
+    next_id: u64,
+    pending: HashSet<u64>,
+    /// Responses read by `poll` for an id other than the one it was called
+    /// on behalf of, held here so they aren't lost when that id is later
+    /// polled for (by `render`, or by a caller driving `submit`/`poll`
+    /// itself).
+    buffered: HashMap<u64, Result<RenderResult, MermkitError>>,
+    binary_transport: bool,
 }
 
 impl Client {
-    pub fn new() -> Result<Self, String> {
+    pub fn new() -> Result<Self, MermkitError> {
         let mut child = Command::new(get_binary())
             .arg("serve")
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .spawn()
-            .map_err(|e| e.to_string())?;
+            .map_err(MermkitError::Spawn)?;
 
-        let stdin = child.stdin.take().ok_or_else(|| "failed to open stdin".to_string())?;
-        let stdout = child.stdout.take().ok_or_else(|| "failed to open stdout".to_string())?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| MermkitError::Protocol("failed to open stdin".to_string()))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| MermkitError::Protocol("failed to open stdout".to_string()))?;
         Ok(Self {
             child,
             stdin,
             stdout: BufReader::new(stdout),
+            next_id: 1,
+            pending: HashSet::new(),
+            buffered: HashMap::new(),
+            binary_transport: false,
         })
     }
 
-    pub fn render(
+    /// Ask the subprocess to send response payloads as a length-prefixed
+    /// raw byte stream instead of base64, avoiding the ~33% size blow-up
+    /// for large SVG/PNG/PDF output. Affects every `render` sent after this
+    /// call; requires a `mermkit` binary new enough to understand
+    /// `"transport":"binary"`.
+    pub fn enable_binary_transport(&mut self) {
+        self.binary_transport = true;
+    }
+
+    /// Write a `render` request to the subprocess and return its request id
+    /// without waiting for a response, so several renders can be pipelined
+    /// against one `serve` process before any results are read back.
+    pub fn submit(
         &mut self,
         source: &str,
         format: &str,
         theme: Option<&str>,
         engine: Option<&str>,
-    ) -> Result<RenderResult, String> {
-        let mut options = serde_json::Map::new();
-        options.insert("format".to_string(), serde_json::Value::String(format.to_string()));
-        if let Some(t) = theme {
-            options.insert("theme".to_string(), serde_json::Value::String(t.to_string()));
+    ) -> Result<u64, MermkitError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let request = build_render_request(id, source, format, theme, engine, self.binary_transport);
+        let line = serde_json::to_string(&request).map_err(|e| MermkitError::Protocol(e.to_string()))?;
+        self.stdin
+            .write_all(line.as_bytes())
+            .map_err(|e| MermkitError::Protocol(e.to_string()))?;
+        self.stdin.write_all(b"\n").map_err(|e| MermkitError::Protocol(e.to_string()))?;
+        self.stdin.flush().map_err(|e| MermkitError::Protocol(e.to_string()))?;
+
+        self.pending.insert(id);
+        Ok(id)
+    }
+
+    /// Read the next response line from the subprocess and dispatch it to
+    /// the pending request it answers. Returns the id so a caller juggling
+    /// several in-flight renders can match it back up; the outer `Result`
+    /// covers failures that aren't attributable to any particular id (a
+    /// closed pipe, a malformed line), while the inner one carries the
+    /// per-request outcome.
+    ///
+    /// In binary transport mode, a response line that announces a payload
+    /// is immediately followed on the same pipe by exactly `byte_len` raw
+    /// bytes, which this reads with `read_exact` before returning so the
+    /// next `poll` starts cleanly at the next response line.
+    ///
+    /// If an earlier `poll` already read a response for some other id and
+    /// buffered it (see [`render`](Client::render)), that buffered response
+    /// is returned before anything new is read off the wire.
+    pub fn poll(&mut self) -> Result<(u64, Result<RenderResult, MermkitError>), MermkitError> {
+        if let Some(&id) = self.buffered.keys().next() {
+            let result = self.buffered.remove(&id).expect("id was just read from this map");
+            return Ok((id, result));
         }
-        if let Some(e) = engine {
-            options.insert("engine".to_string(), serde_json::Value::String(e.to_string()));
+
+        let mut response_line = String::new();
+        let n = self
+            .stdout
+            .read_line(&mut response_line)
+            .map_err(|e| MermkitError::Protocol(e.to_string()))?;
+        if n == 0 {
+            return Err(MermkitError::UnexpectedEof);
         }
 
-        let request = serde_json::json!({
-            "action": "render",
-            "diagram": source,
-            "options": options
-        });
+        let (id, outcome) = parse_rpc_line(&response_line)?;
 
-        let line = serde_json::to_string(&request).map_err(|e| e.to_string())?;
-        self.stdin.write_all(line.as_bytes()).map_err(|e| e.to_string())?;
-        self.stdin.write_all(b"\n").map_err(|e| e.to_string())?;
-        self.stdin.flush().map_err(|e| e.to_string())?;
+        // Consume any trailing binary payload before checking the id, even
+        // for a response we're about to reject as unknown: those bytes are
+        // already on the pipe, and leaving them unread would desync the
+        // next `poll` into misparsing them as the start of a JSON line.
+        let result = match outcome {
+            RenderOutcome::Ready(result) => result,
+            RenderOutcome::Binary { mime, warnings, byte_len } => {
+                let mut bytes = vec![0u8; byte_len];
+                match self.stdout.read_exact(&mut bytes) {
+                    Ok(()) => Ok(RenderResult { bytes, mime, warnings }),
+                    Err(e) => Err(MermkitError::Protocol(format!(
+                        "failed to read {byte_len}-byte binary payload: {e}"
+                    ))),
+                }
+            }
+        };
 
-        let mut response_line = String::new();
-        self.stdout.read_line(&mut response_line).map_err(|e| e.to_string())?;
-        let resp: ServeResponse = serde_json::from_str(&response_line).map_err(|e| e.to_string())?;
-        if !resp.ok {
-            return Err(resp.error.unwrap_or_else(|| "mermkit render failed".to_string()));
+        if !self.pending.remove(&id) {
+            return Err(MermkitError::Protocol(format!("response for unknown request id {id}")));
         }
 
-        let payload = resp.result.ok_or_else(|| "missing result".to_string())?;
-        let bytes_b64 = payload.bytes.ok_or_else(|| "mermkit render returned no bytes".to_string())?;
-        let bytes = STANDARD.decode(bytes_b64).map_err(|e| e.to_string())?;
+        Ok((id, result))
+    }
 
-        Ok(RenderResult {
-            bytes,
-            mime: payload.mime.unwrap_or_else(|| "application/octet-stream".to_string()),
-            warnings: payload.warnings.unwrap_or_default(),
-        })
+    /// Convenience wrapper over [`submit`](Client::submit)/[`poll`](Client::poll)
+    /// for callers that just want one render at a time. Tolerates responses
+    /// to other in-flight ids arriving first, in case this `Client` is
+    /// shared with code that's pipelining its own requests: any such
+    /// response is buffered rather than discarded, so its actual owner can
+    /// still retrieve it from a later `poll` or `render` call.
+    pub fn render(
+        &mut self,
+        source: &str,
+        format: &str,
+        theme: Option<&str>,
+        engine: Option<&str>,
+    ) -> Result<RenderResult, MermkitError> {
+        let id = self.submit(source, format, theme, engine)?;
+        if let Some(result) = self.buffered.remove(&id) {
+            return result;
+        }
+        // Set other ids' responses aside while waiting, so `poll` reads the
+        // wire instead of handing back the same buffered response again.
+        let mut held = std::mem::take(&mut self.buffered);
+        let outcome = loop {
+            match self.poll() {
+                Ok((got_id, result)) if got_id == id => break Ok(result),
+                Ok((got_id, result)) => {
+                    held.insert(got_id, result);
+                }
+                Err(e) => break Err(e),
+            }
+        };
+        self.buffered = held;
+        outcome?
+    }
+}
+
+impl Drop for Client {
+    /// Kill the `serve` subprocess rather than leaving it to notice EOF on
+    /// stdin on its own — load-bearing for long-lived users like [`Pool`],
+    /// whose workers keep a `Client` alive for the pool's whole lifetime.
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
     }
 }
 
-pub fn render(source: &str, format: &str, theme: Option<&str>, engine: Option<&str>) -> Result<RenderResult, String> {
+pub fn render(
+    source: &str,
+    format: &str,
+    theme: Option<&str>,
+    engine: Option<&str>,
+) -> Result<RenderResult, MermkitError> {
     let mut args = vec!["render", "--stdin", "--format", format, "--json"];
     if let Some(t) = theme {
         args.push("--theme");
@@ -113,22 +358,32 @@ pub fn render(source: &str, format: &str, theme: Option<&str>, engine: Option<&s
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
-        .map_err(|e| e.to_string())?;
+        .map_err(MermkitError::Spawn)?;
 
     if let Some(stdin) = child.stdin.as_mut() {
         use std::io::Write;
-        stdin.write_all(source.as_bytes()).map_err(|e| e.to_string())?;
+        stdin
+            .write_all(source.as_bytes())
+            .map_err(|e| MermkitError::Protocol(e.to_string()))?;
     }
 
-    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    let output = child
+        .wait_with_output()
+        .map_err(|e| MermkitError::Protocol(e.to_string()))?;
     if !output.status.success() {
-        let err = String::from_utf8_lossy(&output.stderr);
-        return Err(err.trim().to_string());
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(MermkitError::ExitStatus {
+            code: output.status.code(),
+            stderr,
+        });
     }
 
-    let payload: RenderPayload = serde_json::from_slice(&output.stdout).map_err(|e| e.to_string())?;
-    let bytes_b64 = payload.bytes.ok_or_else(|| "mermkit render returned no bytes".to_string())?;
-    let bytes = STANDARD.decode(bytes_b64).map_err(|e| e.to_string())?;
+    let payload: RenderPayload =
+        serde_json::from_slice(&output.stdout).map_err(|e| MermkitError::Protocol(e.to_string()))?;
+    let bytes_b64 = payload
+        .bytes
+        .ok_or_else(|| MermkitError::Protocol("mermkit render returned no bytes".to_string()))?;
+    let bytes = STANDARD.decode(bytes_b64)?;
 
     Ok(RenderResult {
         bytes,
@@ -140,3 +395,57 @@ pub fn render(source: &str, format: &str, theme: Option<&str>, engine: Option<&s
 fn get_binary() -> String {
     env::var("MERMKIT_BIN").unwrap_or_else(|_| "mermkit".to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rpc_line_decodes_base64_payload() {
+        let line = r#"{"jsonrpc":"2.0","id":1,"result":{"bytes":"aGk=","mime":"image/svg+xml","warnings":["w"]}}"#;
+        let (id, outcome) = parse_rpc_line(line).unwrap();
+        assert_eq!(id, 1);
+        match outcome {
+            RenderOutcome::Ready(Ok(result)) => {
+                assert_eq!(result.bytes, b"hi");
+                assert_eq!(result.mime, "image/svg+xml");
+                assert_eq!(result.warnings, vec!["w".to_string()]);
+            }
+            other => panic!("expected a decoded result, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_rpc_line_reports_a_pending_binary_payload() {
+        let line = r#"{"jsonrpc":"2.0","id":2,"result":{"byte_len":4,"mime":"image/png"}}"#;
+        let (id, outcome) = parse_rpc_line(line).unwrap();
+        assert_eq!(id, 2);
+        match outcome {
+            RenderOutcome::Binary { mime, byte_len, .. } => {
+                assert_eq!(mime, "image/png");
+                assert_eq!(byte_len, 4);
+            }
+            other => panic!("expected a pending binary payload, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_rpc_line_maps_jsonrpc_error_to_render_error() {
+        let line = r#"{"jsonrpc":"2.0","id":3,"error":{"code":-32000,"message":"bad diagram","data":{"class":"SyntaxError"}}}"#;
+        let (id, outcome) = parse_rpc_line(line).unwrap();
+        assert_eq!(id, 3);
+        match outcome {
+            RenderOutcome::Ready(Err(MermkitError::Render { class, message })) => {
+                assert_eq!(class, "SyntaxError");
+                assert_eq!(message, "bad diagram");
+            }
+            other => panic!("expected a Render error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_rpc_line_rejects_a_response_missing_an_id() {
+        let line = r#"{"jsonrpc":"2.0","result":{"bytes":"aGk="}}"#;
+        assert!(parse_rpc_line(line).is_err());
+    }
+}
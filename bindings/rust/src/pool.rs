@@ -0,0 +1,234 @@
+use crate::{Client, MermkitError, RenderResult};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// One diagram to render via [`Pool::render_many`].
+#[derive(Debug, Clone)]
+pub struct RenderJob {
+    pub source: String,
+    pub format: String,
+    pub theme: Option<String>,
+    pub engine: Option<String>,
+}
+
+struct Job {
+    index: usize,
+    job: RenderJob,
+    result_tx: mpsc::Sender<(usize, Result<RenderResult, MermkitError>)>,
+}
+
+/// A fixed-size pool of warm `serve` subprocesses for rendering many
+/// diagrams without paying a full process-spawn cost per diagram.
+///
+/// Jobs submitted through [`render_many`](Pool::render_many) are handed out
+/// to worker threads over a shared queue; each worker keeps its own
+/// [`Client`] alive across jobs and only respawns it if the subprocess
+/// dies.
+pub struct Pool {
+    job_tx: Option<mpsc::Sender<Job>>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl Pool {
+    /// Spawn `size` worker threads (at least one), each owning its own `serve` subprocess
+    /// (started lazily on the first job it receives).
+    pub fn new(size: usize) -> Self {
+        let (job_tx, job_rx) = mpsc::channel::<Job>();
+        let job_rx = Arc::new(Mutex::new(job_rx));
+        let workers = (0..size.max(1))
+            .map(|_| {
+                let job_rx = Arc::clone(&job_rx);
+                thread::spawn(move || worker_loop(job_rx))
+            })
+            .collect();
+
+        Self {
+            job_tx: Some(job_tx),
+            workers,
+        }
+    }
+
+    /// Render every job across the pool's workers, returning results in the
+    /// same order as `jobs` regardless of which worker finished first.
+    pub fn render_many(&self, jobs: &[RenderJob]) -> Vec<Result<RenderResult, MermkitError>> {
+        let job_tx = self.job_tx.as_ref().expect("pool's job queue is still open");
+        let (result_tx, result_rx) = mpsc::channel();
+
+        for (index, job) in jobs.iter().enumerate() {
+            job_tx
+                .send(Job {
+                    index,
+                    job: job.clone(),
+                    result_tx: result_tx.clone(),
+                })
+                .expect("pool worker threads are still alive");
+        }
+        drop(result_tx);
+
+        let mut results: Vec<Option<Result<RenderResult, MermkitError>>> = (0..jobs.len()).map(|_| None).collect();
+        for _ in 0..jobs.len() {
+            let (index, result) = result_rx.recv().expect("a worker dropped its result sender without replying");
+            results[index] = Some(result);
+        }
+
+        results
+            .into_iter()
+            .map(|r| r.expect("every submitted job index receives exactly one result"))
+            .collect()
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        self.job_tx.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn worker_loop(job_rx: Arc<Mutex<mpsc::Receiver<Job>>>) {
+    let mut client: Option<Client> = None;
+    loop {
+        let job = {
+            let rx = job_rx.lock().expect("pool job queue mutex poisoned");
+            rx.recv()
+        };
+        let Ok(job) = job else {
+            break;
+        };
+
+        let result = render_with_respawn(&mut client, &job.job);
+        let _ = job.result_tx.send((job.index, result));
+    }
+}
+
+/// Render one job on a worker's warm `Client` (spawning one first if the
+/// worker has none yet), respawning the subprocess and retrying exactly
+/// once if this job's attempt fails with `UnexpectedEof` before surfacing
+/// an error for this job.
+fn render_with_respawn(client: &mut Option<Client>, job: &RenderJob) -> Result<RenderResult, MermkitError> {
+    retry_once_after_eof(client, Client::new, |c| {
+        c.render(&job.source, &job.format, job.theme.as_deref(), job.engine.as_deref())
+    })
+}
+
+/// Run `attempt` against `*slot`, spawning it via `spawn` first if empty,
+/// and retrying exactly once — respawning first — if `attempt` failed with
+/// `MermkitError::UnexpectedEof`. Generic over the slot type so the
+/// retry-once policy can be unit-tested without a real subprocess.
+fn retry_once_after_eof<T>(
+    slot: &mut Option<T>,
+    spawn: impl Fn() -> Result<T, MermkitError>,
+    attempt: impl Fn(&mut T) -> Result<RenderResult, MermkitError>,
+) -> Result<RenderResult, MermkitError> {
+    if slot.is_none() {
+        *slot = Some(spawn()?);
+    }
+
+    match attempt(slot.as_mut().unwrap()) {
+        Err(MermkitError::UnexpectedEof) => {
+            *slot = Some(spawn()?);
+            attempt(slot.as_mut().unwrap())
+        }
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    struct FakeWorker(u32);
+
+    fn ok_result(tag: u32) -> Result<RenderResult, MermkitError> {
+        Ok(RenderResult {
+            bytes: vec![tag as u8],
+            mime: "image/svg+xml".to_string(),
+            warnings: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn spawns_lazily_when_the_slot_starts_empty() {
+        let mut slot: Option<FakeWorker> = None;
+        let spawns = Cell::new(0);
+
+        let result = retry_once_after_eof(
+            &mut slot,
+            || {
+                spawns.set(spawns.get() + 1);
+                Ok(FakeWorker(spawns.get()))
+            },
+            |w| ok_result(w.0),
+        );
+
+        assert!(result.is_ok());
+        assert_eq!(spawns.get(), 1);
+    }
+
+    #[test]
+    fn respawns_and_retries_exactly_once_after_unexpected_eof() {
+        let mut slot: Option<FakeWorker> = Some(FakeWorker(1));
+        let spawns = Cell::new(0);
+        let attempts = Cell::new(0);
+
+        let result = retry_once_after_eof(
+            &mut slot,
+            || {
+                spawns.set(spawns.get() + 1);
+                Ok(FakeWorker(spawns.get()))
+            },
+            |w| {
+                attempts.set(attempts.get() + 1);
+                if attempts.get() == 1 {
+                    Err(MermkitError::UnexpectedEof)
+                } else {
+                    ok_result(w.0)
+                }
+            },
+        );
+
+        assert!(result.is_ok());
+        assert_eq!(spawns.get(), 1, "should respawn exactly once after the dead subprocess");
+        assert_eq!(attempts.get(), 2, "should retry exactly once after respawning");
+    }
+
+    #[test]
+    fn gives_up_after_the_retry_also_fails() {
+        let mut slot: Option<FakeWorker> = Some(FakeWorker(1));
+        let attempts = Cell::new(0);
+
+        let result = retry_once_after_eof(
+            &mut slot,
+            || Ok(FakeWorker(0)),
+            |_| {
+                attempts.set(attempts.get() + 1);
+                Err(MermkitError::UnexpectedEof)
+            },
+        );
+
+        assert!(matches!(result, Err(MermkitError::UnexpectedEof)));
+        assert_eq!(attempts.get(), 2, "no further retries after the respawned attempt also fails");
+    }
+
+    #[test]
+    fn does_not_retry_on_a_non_eof_error() {
+        let mut slot: Option<FakeWorker> = Some(FakeWorker(1));
+        let attempts = Cell::new(0);
+
+        let result = retry_once_after_eof(
+            &mut slot,
+            || Ok(FakeWorker(0)),
+            |_| {
+                attempts.set(attempts.get() + 1);
+                Err(MermkitError::Protocol("bad diagram".to_string()))
+            },
+        );
+
+        assert!(matches!(result, Err(MermkitError::Protocol(_))));
+        assert_eq!(attempts.get(), 1);
+    }
+}